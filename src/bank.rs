@@ -1,9 +1,15 @@
+use crate::errors::LedgerError;
+use crate::ledger_store::{InMemoryLedgerStore, LedgerRecord, LedgerStore};
+use crate::money::Money;
+use rayon::prelude::*;
 use serde::Deserialize;
 use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Read, Write};
 #[cfg(test)]
 use rstest::*;
 
-#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
 #[allow(non_camel_case_types)]
 pub enum TransactionType {
     deposit,
@@ -13,40 +19,142 @@ pub enum TransactionType {
     chargeback,
 }
 
-#[derive(Debug, Clone, Deserialize)]
-pub struct Transaction {
-    pub kind: TransactionType,
-    pub client: u16,
-    pub tx: u32,
-    pub amount: Option<f32>,
-    #[serde(default)]
-    has_been_disputed: bool,
-    #[serde(default)]
-    has_been_resolved: bool,
+/// The raw shape of a CSV row, before it is known whether the amount column
+/// it carries is actually required for its `kind`.
+#[derive(Debug, Deserialize)]
+pub(crate) struct TransactionRecord {
+    kind: TransactionType,
+    client: u16,
+    tx: u32,
+    amount: Option<Money>,
+}
+
+/// Failure to turn a [`TransactionRecord`] into a [`Transaction`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ParseError {
+    MissingAmount,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::MissingAmount => write!(f, "Amount is missing"),
+        }
+    }
+}
+
+/// A validated instruction from the input stream. `Deposit`/`Withdrawal`
+/// carry the amount they operate on; the dispute-family variants only ever
+/// reference a prior transaction, so they carry none. This makes "deposit
+/// with no amount" and "dispute with an amount" unrepresentable.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transaction {
+    Deposit { client: u16, tx: u32, amount: Money },
+    Withdrawal { client: u16, tx: u32, amount: Money },
+    Dispute { client: u16, tx: u32 },
+    Resolve { client: u16, tx: u32 },
+    Chargeback { client: u16, tx: u32 },
+}
+
+impl TryFrom<TransactionRecord> for Transaction {
+    type Error = ParseError;
+
+    fn try_from(record: TransactionRecord) -> Result<Self, Self::Error> {
+        match record.kind {
+            TransactionType::deposit => Ok(Transaction::Deposit {
+                client: record.client,
+                tx: record.tx,
+                amount: record.amount.ok_or(ParseError::MissingAmount)?,
+            }),
+            TransactionType::withdrawal => Ok(Transaction::Withdrawal {
+                client: record.client,
+                tx: record.tx,
+                amount: record.amount.ok_or(ParseError::MissingAmount)?,
+            }),
+            TransactionType::dispute => Ok(Transaction::Dispute {
+                client: record.client,
+                tx: record.tx,
+            }),
+            TransactionType::resolve => Ok(Transaction::Resolve {
+                client: record.client,
+                tx: record.tx,
+            }),
+            TransactionType::chargeback => Ok(Transaction::Chargeback {
+                client: record.client,
+                tx: record.tx,
+            }),
+        }
+    }
 }
 
 impl Transaction {
-    #[cfg(test)]
-    pub fn new(kind: TransactionType, client: u16, tx: u32, amount: Option<f32>) -> Self {
+    /// The client a transaction applies to, used to shard work across
+    /// threads without touching each transaction's ledger/funds logic.
+    pub fn client(&self) -> u16 {
+        match *self {
+            Transaction::Deposit { client, .. }
+            | Transaction::Withdrawal { client, .. }
+            | Transaction::Dispute { client, .. }
+            | Transaction::Resolve { client, .. }
+            | Transaction::Chargeback { client, .. } => client,
+        }
+    }
+}
+
+#[cfg(test)]
+impl TransactionRecord {
+    pub fn new(kind: TransactionType, client: u16, tx: u32, amount: Option<Money>) -> Self {
         Self {
             kind,
             client,
             tx,
             amount,
-            has_been_disputed: false,
-            has_been_resolved: false,
         }
     }
 }
+
+/// Where a transaction sits in its dispute lifecycle. Only deposits and
+/// withdrawals are tracked through this state machine; `Processed` is the
+/// state every stored transaction starts in.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TxState {
+    #[default]
+    Processed,
+    Disputed,
+    Resolved,
+    ChargedBack,
+}
+
+impl TxState {
+    pub(crate) fn to_byte(self) -> u8 {
+        match self {
+            TxState::Processed => 0,
+            TxState::Disputed => 1,
+            TxState::Resolved => 2,
+            TxState::ChargedBack => 3,
+        }
+    }
+
+    pub(crate) fn from_byte(byte: u8) -> Self {
+        match byte {
+            0 => TxState::Processed,
+            1 => TxState::Disputed,
+            2 => TxState::Resolved,
+            3 => TxState::ChargedBack,
+            _ => unreachable!("corrupt ledger record: invalid TxState byte {byte}"),
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct Funds {
-    available: f32,
-    held: f32,
+    available: Money,
+    held: Money,
     is_locked: bool,
 }
 
 impl Funds {
-    pub fn new(available: f32, held: f32) -> Self {
+    pub fn new(available: Money, held: Money) -> Self {
         Self {
             available,
             held,
@@ -55,160 +163,332 @@ impl Funds {
     }
 }
 
-pub struct Bank {
+pub struct Bank<S: LedgerStore = InMemoryLedgerStore> {
     pub accounts: HashMap<u16, Funds>,
-    pub ledger: HashMap<u32, Transaction>,
+    ledger: S,
 }
 
-impl Bank {
-    pub fn process_transaction(&mut self, transaction: &Transaction) -> Result<(), &str> {
-        match transaction.kind {
-            TransactionType::deposit => {
-                self.credit_account(transaction.client, transaction.amount.unwrap())
-            }
-            TransactionType::withdrawal => {
-                self.debit_account(transaction.client, transaction.amount.unwrap())
-            }
-            TransactionType::dispute => {
-                self.dispute_transaction(transaction.client, transaction.tx)
+impl Bank<InMemoryLedgerStore> {
+    pub fn new() -> Self {
+        Self::with_store(InMemoryLedgerStore::default())
+    }
+
+    /// Processes every transaction, sharding by client so that each
+    /// client's history is handled in order on its own thread while
+    /// unrelated clients run concurrently. Clients never interact in a
+    /// *valid* stream (a dispute can only ever reference a transaction
+    /// from the same client), so sharding this way is sound and needs no
+    /// locking. Processing errors are reported to `errors`, the same
+    /// injected-writer pattern [`Bank::run`] uses.
+    ///
+    /// One diagnostic deliberately differs from the sequential path on
+    /// malformed input: each shard only ever sees its own client's
+    /// transactions, so a dispute referencing another client's tx comes
+    /// back as `LedgerError::UnknownTx` here rather than the
+    /// `DisputeClientMismatch` the sequential path gives (it can see the
+    /// whole ledger). Telling the two apart would need a cross-client tx
+    /// index built before sharding, which defeats sharding's purpose of
+    /// keeping shards independent.
+    pub fn process_parallel<I, W: Write>(transactions: I, errors: &mut W) -> io::Result<Self>
+    where
+        I: IntoIterator<Item = Transaction>,
+    {
+        let mut shards: HashMap<u16, Vec<Transaction>> = HashMap::new();
+        for transaction in transactions {
+            shards.entry(transaction.client()).or_default().push(transaction);
+        }
+
+        let results: Vec<(Bank<InMemoryLedgerStore>, Vec<String>)> = shards
+            .into_par_iter()
+            .map(|(_, client_transactions)| {
+                let mut bank = Bank::new();
+                let mut shard_errors = Vec::new();
+                for transaction in &client_transactions {
+                    match bank.process_transaction(transaction) {
+                        Ok(()) => bank.add_transaction_to_ledger(transaction),
+                        Err(e) => shard_errors.push(e.to_string()),
+                    }
+                }
+                (bank, shard_errors)
+            })
+            .collect();
+
+        let mut merged = Bank::new();
+        for (partial, shard_errors) in results {
+            merged.accounts.extend(partial.accounts);
+            for e in shard_errors {
+                writeln!(errors, "{}", e)?;
             }
-            TransactionType::resolve => {
-                self.resolve_transaction(transaction.client, transaction.tx)
+        }
+        Ok(merged)
+    }
+}
+
+impl<S: LedgerStore> Bank<S> {
+    pub fn with_store(ledger: S) -> Self {
+        Self {
+            accounts: HashMap::new(),
+            ledger,
+        }
+    }
+
+    pub fn process_transaction(&mut self, transaction: &Transaction) -> Result<(), LedgerError> {
+        match *transaction {
+            Transaction::Deposit { client, amount, .. } => self.credit_account(client, amount),
+            Transaction::Withdrawal { client, amount, .. } => self.debit_account(client, amount),
+            Transaction::Dispute { client, tx } => self.dispute_transaction(client, tx),
+            Transaction::Resolve { client, tx } => self.resolve_transaction(client, tx),
+            Transaction::Chargeback { client, tx } => self.chargeback_transaction(client, tx),
+        }
+    }
+
+    pub fn add_transaction_to_ledger(&mut self, transaction: &Transaction) {
+        match *transaction {
+            Transaction::Deposit { client, tx, amount } => {
+                self.ledger.insert(tx, LedgerRecord::new(client, amount));
             }
-            TransactionType::chargeback => {
-                self.chargeback_transaction(transaction.client, transaction.tx)
+            // Stored negative so a later dispute holds money that left the
+            // account, rather than money that never left it.
+            Transaction::Withdrawal { client, tx, amount } => {
+                self.ledger.insert(tx, LedgerRecord::new(client, -amount));
             }
+            Transaction::Dispute { .. } | Transaction::Resolve { .. } | Transaction::Chargeback { .. } => {}
         }
     }
 
-    pub fn add_transaction_to_ledger(&mut self, transaction: Transaction) {
-        self.ledger.insert(transaction.tx, transaction);
+    /// Reads every transaction from `reader` and applies it to this ledger
+    /// one row at a time, as it is deserialized, so `reader` is never held
+    /// in memory as a whole (the ledger store still keeps a record of every
+    /// applied deposit and withdrawal). Can be called repeatedly, once per input,
+    /// to fold several files (or stdin followed by files) into one shared
+    /// `Bank`. Parse and processing failures are reported to `errors`
+    /// rather than stopping the stream.
+    pub fn run<R: Read, W: Write>(&mut self, reader: R, errors: &mut W) -> io::Result<()> {
+        let mut rdr = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .flexible(true)
+            .from_reader(reader);
+
+        for record in rdr.deserialize() {
+            if let Some(transaction) = parse_record(record, errors)? {
+                match self.process_transaction(&transaction) {
+                    Ok(()) => self.add_transaction_to_ledger(&transaction),
+                    Err(e) => writeln!(errors, "{}", e)?,
+                }
+            }
+        }
+        Ok(())
     }
 
-    pub fn print_accounts(&self) {
-        println!("client, available, held, total, locked");
+    pub fn print_accounts<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "client, available, held, total, locked")?;
         for (client, funds) in self.accounts.iter() {
-            println!(
-                "{},{:.4},{:.4},{:.4},{}",
-                client,
-                funds.available,
-                funds.held,
-                funds.available + funds.held,
-                funds.is_locked
-            )
+            let total = funds.available.checked_add(funds.held).unwrap();
+            writeln!(
+                writer,
+                "{},{},{},{},{}",
+                client, funds.available, funds.held, total, funds.is_locked
+            )?;
         }
+        Ok(())
     }
 
-    fn credit_account(&mut self, client: u16, amount: f32) -> Result<(), &str> {
-        let funds = self.accounts.entry(client).or_insert(Funds::new(0.0, 0.0));
+    fn credit_account(&mut self, client: u16, amount: Money) -> Result<(), LedgerError> {
+        let funds = self
+            .accounts
+            .entry(client)
+            .or_insert(Funds::new(Money::ZERO, Money::ZERO));
         if funds.is_locked {
-            return Err("Account frozen");
+            return Err(LedgerError::AccountFrozen);
         };
 
-        if funds.available + amount < f32::MAX {
-            funds.available += amount;
-            Ok(())
-        } else {
-            Err("Upper limit reached, time to give to charity?")
+        match funds.available.checked_add(amount) {
+            Some(new_available) => {
+                funds.available = new_available;
+                Ok(())
+            }
+            None => Err(LedgerError::UpperLimitReached),
         }
     }
 
-    fn debit_account(&mut self, client: u16, amount: f32) -> Result<(), &str> {
-        let funds = self.accounts.entry(client).or_insert(Funds::new(0.0, 0.0));
+    fn debit_account(&mut self, client: u16, amount: Money) -> Result<(), LedgerError> {
+        let funds = self
+            .accounts
+            .entry(client)
+            .or_insert(Funds::new(Money::ZERO, Money::ZERO));
         if funds.is_locked {
-            return Err("Account frozen");
+            return Err(LedgerError::AccountFrozen);
         };
-        if funds.available > amount {
-            funds.available -= amount;
+        if funds.available >= amount {
+            funds.available = funds.available.checked_sub(amount).unwrap();
             Ok(())
 
         } else {
-            Err("Insufficient funds")
+            Err(LedgerError::NotEnoughFunds)
         }
     }
 
-    fn dispute_transaction(&mut self, client: u16, tx: u32) -> Result<(), &str> {
-        let disputed_transaction =
-            check_for_valid_disputed_transaction(&mut self.ledger, client, tx)?;
-        if disputed_transaction.has_been_disputed {
-            return Err("Transaction is already disputed");
+    /// Disputing a deposit moves the deposited amount from `available` into
+    /// `held`, so the total stays unchanged. A withdrawal's stored amount is
+    /// negative and disputing it leaves `available` alone, so the same
+    /// `checked_add` against `held` just records the withdrawn amount as a
+    /// deficit there. `resolve` below clears that deficit; `chargeback`
+    /// clears it and refunds the withdrawn amount to `available`.
+    fn dispute_transaction(&mut self, client: u16, tx: u32) -> Result<(), LedgerError> {
+        let disputed_transaction = lookup_disputed_transaction(&self.ledger, client, tx)?;
+        match disputed_transaction.state {
+            TxState::Processed => {}
+            TxState::Disputed => return Err(LedgerError::AlreadyDisputed),
+            TxState::Resolved => return Err(LedgerError::AlreadyResolved),
+            TxState::ChargedBack => return Err(LedgerError::AlreadyChargedBack),
         };
 
         if let Some(funds) = self.accounts.get_mut(&client) {
             if funds.is_locked {
-                return Err("Account frozen");
+                return Err(LedgerError::AccountFrozen);
             };
-            funds.held += disputed_transaction.amount.unwrap();
-            disputed_transaction.has_been_disputed = true;
+            let amount = disputed_transaction.amount;
+            if amount >= Money::ZERO {
+                funds.available = funds
+                    .available
+                    .checked_sub(amount)
+                    .ok_or(LedgerError::UpperLimitReached)?;
+            }
+            funds.held = funds
+                .held
+                .checked_add(amount)
+                .ok_or(LedgerError::UpperLimitReached)?;
+            self.ledger.update_state(tx, TxState::Disputed);
             Ok(())
 
         } else {
-            return Err("Client not found");
+            return Err(LedgerError::ClientNotFound);
         }
     }
 
-    fn resolve_transaction(&mut self, client: u16, tx: u32) -> Result<(), &str> {
-        let disputed_transaction =
-            check_for_valid_disputed_transaction(&mut self.ledger, client, tx)?;
-        if !disputed_transaction.has_been_disputed {
-            return Err("Transaction is not disputed")
-        };
-        if disputed_transaction.has_been_resolved {
-            return Err("Transaction already resolved")
+    fn resolve_transaction(&mut self, client: u16, tx: u32) -> Result<(), LedgerError> {
+        let disputed_transaction = lookup_disputed_transaction(&self.ledger, client, tx)?;
+        match disputed_transaction.state {
+            TxState::Disputed => {}
+            TxState::Processed => return Err(LedgerError::NotDisputed),
+            TxState::Resolved => return Err(LedgerError::AlreadyResolved),
+            TxState::ChargedBack => return Err(LedgerError::AlreadyChargedBack),
         };
 
         if let Some(funds) = self.accounts.get_mut(&client) {
             if funds.is_locked {
-                return Err("Account frozen");
+                return Err(LedgerError::AccountFrozen);
             };
-            funds.available += disputed_transaction.amount.unwrap();
-            funds.held -= disputed_transaction.amount.unwrap();
-            disputed_transaction.has_been_resolved = true;
+            let amount = disputed_transaction.amount;
+            // A positive amount is a disputed deposit: the held funds go
+            // back to `available`. A negative amount is a disputed
+            // withdrawal, whose dispute never touched `available`, so
+            // resolving it just clears the hold below.
+            if amount >= Money::ZERO {
+                funds.available = funds
+                    .available
+                    .checked_add(amount)
+                    .ok_or(LedgerError::UpperLimitReached)?;
+            }
+            funds.held = funds.held.checked_sub(amount).unwrap();
+            self.ledger.update_state(tx, TxState::Resolved);
             Ok(())
 
         } else {
-            return Err("Client not found");
+            return Err(LedgerError::ClientNotFound);
         }
     }
 
-    fn chargeback_transaction(&mut self, client: u16, tx: u32) -> Result<(), &str> {
-        let disputed_transaction =
-            check_for_valid_disputed_transaction(&mut self.ledger, client, tx)?;
-        if !disputed_transaction.has_been_disputed {
-            return Err("Transaction is not disputed");
-        };
-        if disputed_transaction.has_been_resolved {
-            return Err("Transaction already resolved")
+    fn chargeback_transaction(&mut self, client: u16, tx: u32) -> Result<(), LedgerError> {
+        let disputed_transaction = lookup_disputed_transaction(&self.ledger, client, tx)?;
+        match disputed_transaction.state {
+            TxState::Disputed => {}
+            TxState::Processed => return Err(LedgerError::NotDisputed),
+            TxState::Resolved => return Err(LedgerError::AlreadyResolved),
+            TxState::ChargedBack => return Err(LedgerError::AlreadyChargedBack),
         };
 
         if let Some(funds) = self.accounts.get_mut(&client) {
-            funds.held -= disputed_transaction.amount.unwrap();
+            if funds.is_locked {
+                return Err(LedgerError::AccountFrozen);
+            };
+            let amount = disputed_transaction.amount;
+            // A negative amount is a disputed withdrawal being charged
+            // back: the client is refunded the money that had left their
+            // account. A positive amount (deposit) never added to
+            // `available` at dispute time, so nothing is refunded there.
+            if amount < Money::ZERO {
+                funds.available = funds
+                    .available
+                    .checked_sub(amount)
+                    .ok_or(LedgerError::UpperLimitReached)?;
+            }
+            funds.held = funds.held.checked_sub(amount).unwrap();
             funds.is_locked = true;
-            disputed_transaction.has_been_disputed = false;
+            self.ledger.update_state(tx, TxState::ChargedBack);
             Ok(())
 
         } else {
-            return Err("Client not found");
+            return Err(LedgerError::ClientNotFound);
         }
     }
 }
 
-fn check_for_valid_disputed_transaction(
-    ledger: &mut HashMap<u32, Transaction>,
+/// Turns one deserialized CSV row into a [`Transaction`], reporting (rather
+/// than propagating) a row that fails to parse or fails to become a valid
+/// transaction, so a single bad row doesn't abort the stream.
+fn parse_record<W: Write>(
+    record: Result<TransactionRecord, csv::Error>,
+    errors: &mut W,
+) -> io::Result<Option<Transaction>> {
+    match record {
+        Ok(record) => match Transaction::try_from(record) {
+            Ok(transaction) => Ok(Some(transaction)),
+            Err(e) => {
+                writeln!(errors, "{}", e)?;
+                Ok(None)
+            }
+        },
+        Err(e) => {
+            writeln!(errors, "{}", e)?;
+            Ok(None)
+        }
+    }
+}
+
+/// Parses every CSV transaction record from `reader` into memory. Used by
+/// `--parallel`, which must see the whole stream before it can shard it by
+/// client; [`Bank::run`] applies rows one at a time instead and should be
+/// preferred whenever the input doesn't need to be sharded first.
+pub fn parse_transactions<R: Read, W: Write>(
+    reader: R,
+    errors: &mut W,
+) -> io::Result<Vec<Transaction>> {
+    let mut rdr = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(reader);
+
+    let mut transactions = Vec::new();
+    for record in rdr.deserialize() {
+        if let Some(transaction) = parse_record(record, errors)? {
+            transactions.push(transaction);
+        }
+    }
+    Ok(transactions)
+}
+
+fn lookup_disputed_transaction<S: LedgerStore>(
+    ledger: &S,
     client: u16,
     tx: u32,
-) -> Result<&mut Transaction, &'static str> {
-    if let Some(disputed_transaction) = ledger.get_mut(&tx) {
-        if disputed_transaction.client != client {
-            return Err("Dispute transaction of another client");
-        } else if disputed_transaction.amount == None {
-            return Err("Invalid transaction");
-        } else {
-            return Ok(disputed_transaction);
-        };
+) -> Result<LedgerRecord, LedgerError> {
+    let disputed_transaction = ledger.get(tx).ok_or(LedgerError::UnknownTx(tx))?;
+    if disputed_transaction.client != client {
+        Err(LedgerError::DisputeClientMismatch)
     } else {
-        return Err("Transaction not found");
-    };
+        Ok(disputed_transaction)
+    }
 }
 
 #[cfg(test)]
@@ -221,16 +501,13 @@ mod tests {
 
     #[fixture]
     pub fn bank() -> Bank {
-        Bank {
-            accounts: HashMap::new(),
-            ledger: HashMap::new(),
-        }
+        Bank::new()
     }
 
     pub fn add_client(bank: &mut Bank) -> Client {
         let client = Client {
             id: 1,
-            funds: Funds::new(40.0, 100.0),
+            funds: Funds::new(Money::from_f64(40.0), Money::from_f64(100.0)),
         };
         bank.accounts.insert(client.id, client.funds.clone());
         client
@@ -240,8 +517,8 @@ mod tests {
         let client = Client {
             id: 1,
             funds: Funds {
-                available: 40.0,
-                held: 100.0,
+                available: Money::from_f64(40.0),
+                held: Money::from_f64(100.0),
                 is_locked: true,
             },
         };
@@ -252,7 +529,7 @@ mod tests {
     pub fn add_rich_client(bank: &mut Bank) -> Client {
         let client = Client {
             id: 1,
-            funds: Funds::new(f32::MAX, 100.0),
+            funds: Funds::new(Money::from_f64(i64::MAX as f64 / 10_000.0), Money::from_f64(100.0)),
         };
         bank.accounts.insert(client.id, client.funds.clone());
         client
@@ -260,58 +537,111 @@ mod tests {
 
     pub fn add_valid_transaction(bank: &mut Bank) -> Transaction {
         let tx = 42_u32;
-        let transaction = Transaction::new(TransactionType::deposit, 1, tx, Some(50.0));
-        bank.ledger.insert(tx, transaction.clone());
+        let transaction = Transaction::Deposit {
+            client: 1,
+            tx,
+            amount: Money::from_f64(50.0),
+        };
+        bank.ledger.insert(tx, LedgerRecord::new(1, Money::from_f64(50.0)));
         transaction
     }
 
-    pub fn add_invalid_transaction(bank: &mut Bank) -> Transaction {
+    pub fn add_valid_withdrawal(bank: &mut Bank) -> Transaction {
         let tx = 42_u32;
-        let transaction = Transaction::new(TransactionType::dispute, 1, tx, None);
-        bank.ledger.insert(tx, transaction.clone());
-        transaction
+        let amount = Money::from_f64(20.0);
+        bank.ledger.insert(tx, LedgerRecord::new(1, -amount));
+        Transaction::Withdrawal {
+            client: 1,
+            tx,
+            amount,
+        }
     }
 
     pub fn add_disputed_transaction(bank: &mut Bank) -> Transaction {
         let tx = 42_u32;
-        let transaction = Transaction {
-            kind: TransactionType::deposit,
+        let amount = Money::from_f64(50.0);
+        bank.ledger.insert(
+            tx,
+            LedgerRecord {
+                client: 1,
+                amount,
+                state: TxState::Disputed,
+            },
+        );
+        Transaction::Deposit {
             client: 1,
             tx,
-            amount: Some(50.0),
-            has_been_disputed: true,
-            has_been_resolved: false,
+            amount,
+        }
+    }
+
+    pub fn add_disputed_withdrawal_transaction(bank: &mut Bank) -> Transaction {
+        let withdrawal = add_valid_withdrawal(bank);
+        let dispute = Transaction::Dispute {
+            client: client_of(&withdrawal),
+            tx: tx_of(&withdrawal),
         };
-        bank.ledger.insert(tx, transaction.clone());
-        transaction
+        bank.process_transaction(&dispute).unwrap();
+        withdrawal
+    }
+
+    fn amount_of(transaction: &Transaction) -> Money {
+        match *transaction {
+            Transaction::Deposit { amount, .. } | Transaction::Withdrawal { amount, .. } => amount,
+            _ => panic!("transaction has no amount"),
+        }
+    }
+
+    fn tx_of(transaction: &Transaction) -> u32 {
+        match *transaction {
+            Transaction::Deposit { tx, .. }
+            | Transaction::Withdrawal { tx, .. }
+            | Transaction::Dispute { tx, .. }
+            | Transaction::Resolve { tx, .. }
+            | Transaction::Chargeback { tx, .. } => tx,
+        }
+    }
+
+    fn client_of(transaction: &Transaction) -> u16 {
+        match *transaction {
+            Transaction::Deposit { client, .. }
+            | Transaction::Withdrawal { client, .. }
+            | Transaction::Dispute { client, .. }
+            | Transaction::Resolve { client, .. }
+            | Transaction::Chargeback { client, .. } => client,
+        }
     }
 
     #[rstest]
     fn credit_non_existing_client_account(mut bank: Bank) {
         let client: u16 = 1;
-        let amount = Some(42.1234);
-        let transaction = Transaction::new(TransactionType::deposit, client, 1, amount);
+        let amount = Money::from_f64(42.1234);
+        let transaction = Transaction::Deposit { client, tx: 1, amount };
 
         bank.process_transaction(&transaction).unwrap();
 
         assert_eq!(
             bank.accounts.get(&client).unwrap(),
-            &Funds::new(amount.unwrap(), 0.0)
+            &Funds::new(amount, Money::ZERO)
         );
     }
 
     #[rstest]
     fn credit_existing_client_account(mut bank: Bank) {
         let existing_client = add_client(&mut bank);
-        let amount = Some(42.1234);
-        let transaction = Transaction::new(TransactionType::deposit, existing_client.id, 1, amount);
+        let amount = Money::from_f64(42.1234);
+        let transaction = Transaction::Deposit {
+            client: existing_client.id,
+            tx: 1,
+            amount,
+        };
 
         bank.process_transaction(&transaction).unwrap();
 
         assert_eq!(
             bank.accounts.get(&existing_client.id).unwrap(),
             &Funds::new(
-                existing_client.funds.available + amount.unwrap(),
+                existing_client.funds.available.checked_add(amount).unwrap(),
                 existing_client.funds.held
             )
         );
@@ -320,58 +650,95 @@ mod tests {
     #[rstest]
     fn credit_frozen_account(mut bank: Bank) {
         let locked_client = add_locked_client(&mut bank);
-        let transaction = Transaction::new(TransactionType::deposit, locked_client.id, 1, Some(42.0));
+        let transaction = Transaction::Deposit {
+            client: locked_client.id,
+            tx: 1,
+            amount: Money::from_f64(42.0),
+        };
 
         let result = bank.process_transaction(&transaction).unwrap_err();
 
-        assert_eq!(result, "Account frozen");
+        assert_eq!(result, LedgerError::AccountFrozen);
     }
 
     #[rstest]
     fn credit_full_account(mut bank: Bank) {
         let rich_client = add_rich_client(&mut bank);
-        let transaction = Transaction::new(TransactionType::deposit, rich_client.id, 1, Some(42.0));
+        let transaction = Transaction::Deposit {
+            client: rich_client.id,
+            tx: 1,
+            amount: Money::from_f64(42.0),
+        };
 
         let result = bank.process_transaction(&transaction).unwrap_err();
 
-        assert_eq!(result, "Upper limit reached, time to give to charity?");
+        assert_eq!(result, LedgerError::UpperLimitReached);
     }
 
     #[rstest]
     fn debit_non_existing_client(mut bank: Bank) {
-        let amount = Some(0.0);
-        let transaction = Transaction::new(TransactionType::withdrawal, 1, 1, amount);
+        let transaction = Transaction::Withdrawal {
+            client: 1,
+            tx: 1,
+            amount: Money::from_f64(1.0),
+        };
 
         let result = bank.process_transaction(&transaction).unwrap_err();
 
-        assert_eq!(result, "Insufficient funds");
+        assert_eq!(result, LedgerError::NotEnoughFunds);
     }
 
     #[rstest]
     fn debit_client_account_with_sufficient_funds(mut bank: Bank) {
         let existing_client = add_client(&mut bank);
-        let amount = Some(10.0);
-        let withdrawal = Transaction::new(TransactionType::withdrawal, existing_client.id, 1, amount);
+        let amount = Money::from_f64(10.0);
+        let withdrawal = Transaction::Withdrawal {
+            client: existing_client.id,
+            tx: 1,
+            amount,
+        };
         bank.process_transaction(&withdrawal).unwrap();
 
         assert_eq!(
             bank.accounts.get(&existing_client.id).unwrap(),
             &Funds::new(
-                existing_client.funds.available - amount.unwrap(),
+                existing_client.funds.available.checked_sub(amount).unwrap(),
                 existing_client.funds.held
             )
         );
     }
 
+    #[rstest]
+    fn debit_client_account_with_exact_available_balance(mut bank: Bank) {
+        let existing_client = add_client(&mut bank);
+        let amount = existing_client.funds.available;
+        let withdrawal = Transaction::Withdrawal {
+            client: existing_client.id,
+            tx: 1,
+            amount,
+        };
+
+        bank.process_transaction(&withdrawal).unwrap();
+
+        assert_eq!(
+            bank.accounts.get(&existing_client.id).unwrap(),
+            &Funds::new(Money::ZERO, existing_client.funds.held)
+        );
+    }
+
     #[rstest]
     fn debit_client_account_with_insufficient_funds(mut bank: Bank) {
         let existing_client = add_client(&mut bank);
-        let amount = Some(9999.9);
-        let withdrawal = Transaction::new(TransactionType::withdrawal, existing_client.id, 1, amount);
+        let amount = Money::from_f64(9999.9);
+        let withdrawal = Transaction::Withdrawal {
+            client: existing_client.id,
+            tx: 1,
+            amount,
+        };
 
         let result = bank.process_transaction(&withdrawal).unwrap_err();
 
-        assert_eq!(result, "Insufficient funds");
+        assert_eq!(result, LedgerError::NotEnoughFunds);
         assert_eq!(
             bank.accounts.get(&existing_client.id).unwrap(),
             &Funds::new(existing_client.funds.available, existing_client.funds.held)
@@ -383,12 +750,43 @@ mod tests {
         let existing_client = add_client(&mut bank);
         let existing_transaction = add_valid_transaction(&mut bank);
 
-        let dispute = Transaction::new(
-            TransactionType::dispute,
-            existing_transaction.client,
-            existing_transaction.tx,
-            None,
+        let dispute = Transaction::Dispute {
+            client: client_of(&existing_transaction),
+            tx: tx_of(&existing_transaction),
+        };
+
+        bank.process_transaction(&dispute).unwrap();
+
+        assert_eq!(
+            bank.accounts.get(&existing_client.id).unwrap(),
+            &Funds::new(
+                existing_client
+                    .funds
+                    .available
+                    .checked_sub(amount_of(&existing_transaction))
+                    .unwrap(),
+                existing_client
+                    .funds
+                    .held
+                    .checked_add(amount_of(&existing_transaction))
+                    .unwrap()
+            )
+        );
+        assert_eq!(
+            bank.ledger.get(tx_of(&existing_transaction)).unwrap().state,
+            TxState::Disputed
         );
+    }
+
+    #[rstest]
+    fn dispute_withdrawal_transaction(mut bank: Bank) {
+        let existing_client = add_client(&mut bank);
+        let disputed_withdrawal = add_valid_withdrawal(&mut bank);
+
+        let dispute = Transaction::Dispute {
+            client: client_of(&disputed_withdrawal),
+            tx: tx_of(&disputed_withdrawal),
+        };
 
         bank.process_transaction(&dispute).unwrap();
 
@@ -396,15 +794,16 @@ mod tests {
             bank.accounts.get(&existing_client.id).unwrap(),
             &Funds::new(
                 existing_client.funds.available,
-                existing_client.funds.held + existing_transaction.amount.unwrap()
+                existing_client
+                    .funds
+                    .held
+                    .checked_sub(amount_of(&disputed_withdrawal))
+                    .unwrap()
             )
         );
         assert_eq!(
-            bank.ledger
-                .get(&existing_transaction.tx)
-                .unwrap()
-                .has_been_disputed,
-            true
+            bank.ledger.get(tx_of(&disputed_withdrawal)).unwrap().state,
+            TxState::Disputed
         );
     }
 
@@ -412,92 +811,132 @@ mod tests {
     fn dispute_existing_transaction_of_another_client(mut bank: Bank) {
         let existing_transaction = add_valid_transaction(&mut bank);
 
-        let dispute = Transaction::new(
-            TransactionType::dispute,
-            existing_transaction.client + 1,
-            existing_transaction.tx,
-            None,
-        );
+        let dispute = Transaction::Dispute {
+            client: client_of(&existing_transaction) + 1,
+            tx: tx_of(&existing_transaction),
+        };
 
         let result = bank.process_transaction(&dispute).unwrap_err();
 
-        assert_eq!(result, "Dispute transaction of another client");
+        assert_eq!(result, LedgerError::DisputeClientMismatch);
     }
 
     #[rstest]
     fn dispute_non_existing_transaction(mut bank: Bank) {
-        let dispute = Transaction::new(TransactionType::dispute, 2, 123456789, None);
+        let dispute = Transaction::Dispute {
+            client: 2,
+            tx: 123456789,
+        };
 
         let result = bank.process_transaction(&dispute).unwrap_err();
 
-        assert_eq!(result, "Transaction not found");
+        assert_eq!(result, LedgerError::UnknownTx(123456789));
     }
 
     #[rstest]
     fn dispute_already_disputed_transaction(mut bank: Bank) {
         add_client(&mut bank);
         let disputed_transaction = add_disputed_transaction(&mut bank);
-        let resolve = Transaction::new(
-            TransactionType::resolve,
-            disputed_transaction.client,
-            disputed_transaction.tx,
-            None,
-        );
+        let dispute = Transaction::Dispute {
+            client: client_of(&disputed_transaction),
+            tx: tx_of(&disputed_transaction),
+        };
+
+        let result = bank.process_transaction(&dispute).unwrap_err();
+
+        assert_eq!(result, LedgerError::AlreadyDisputed);
+    }
+
+    #[rstest]
+    fn dispute_resolved_transaction(mut bank: Bank) {
+        add_client(&mut bank);
+        let disputed_transaction = add_disputed_transaction(&mut bank);
+        let resolve = Transaction::Resolve {
+            client: client_of(&disputed_transaction),
+            tx: tx_of(&disputed_transaction),
+        };
 
         bank.process_transaction(&resolve).unwrap();
-        let dispute = Transaction::new(
-            TransactionType::dispute,
-            disputed_transaction.client,
-            disputed_transaction.tx,
-            None,
-        );
+        let dispute = Transaction::Dispute {
+            client: client_of(&disputed_transaction),
+            tx: tx_of(&disputed_transaction),
+        };
 
         let result = bank.process_transaction(&dispute).unwrap_err();
 
-        assert_eq!(result, "Transaction is already disputed");
+        assert_eq!(result, LedgerError::AlreadyResolved);
     }
 
     #[rstest]
-    fn dispute_invalid_transaction(mut bank: Bank) {
-        let existing_invalid_transaction = add_invalid_transaction(&mut bank);
-        let dispute = Transaction::new(
-            TransactionType::dispute,
-            existing_invalid_transaction.client,
-            existing_invalid_transaction.tx,
-            None,
-        );
+    fn dispute_charged_back_transaction_is_rejected(mut bank: Bank) {
+        add_client(&mut bank);
+        let disputed_transaction = add_disputed_transaction(&mut bank);
+        let chargeback = Transaction::Chargeback {
+            client: client_of(&disputed_transaction),
+            tx: tx_of(&disputed_transaction),
+        };
+
+        bank.process_transaction(&chargeback).unwrap();
+        let dispute = Transaction::Dispute {
+            client: client_of(&disputed_transaction),
+            tx: tx_of(&disputed_transaction),
+        };
 
         let result = bank.process_transaction(&dispute).unwrap_err();
 
-        assert_eq!(result, "Invalid transaction");
+        assert_eq!(result, LedgerError::AlreadyChargedBack);
     }
 
     #[rstest]
     fn resolve_disputed_transaction(mut bank: Bank) {
         let existing_client = add_client(&mut bank);
         let disputed_transaction = add_disputed_transaction(&mut bank);
-        let resolve = Transaction::new(
-            TransactionType::resolve,
-            disputed_transaction.client,
-            disputed_transaction.tx,
-            None,
-        );
+        let resolve = Transaction::Resolve {
+            client: client_of(&disputed_transaction),
+            tx: tx_of(&disputed_transaction),
+        };
 
         bank.process_transaction(&resolve).unwrap();
 
         assert_eq!(
             bank.accounts.get(&existing_client.id).unwrap(),
             &Funds::new(
-                existing_client.funds.available + disputed_transaction.amount.unwrap(),
-                existing_client.funds.held - disputed_transaction.amount.unwrap()
+                existing_client
+                    .funds
+                    .available
+                    .checked_add(amount_of(&disputed_transaction))
+                    .unwrap(),
+                existing_client
+                    .funds
+                    .held
+                    .checked_sub(amount_of(&disputed_transaction))
+                    .unwrap()
             )
         );
         assert_eq!(
-            bank.ledger
-                .get(&disputed_transaction.tx)
-                .unwrap()
-                .has_been_resolved,
-            true
+            bank.ledger.get(tx_of(&disputed_transaction)).unwrap().state,
+            TxState::Resolved
+        );
+    }
+
+    #[rstest]
+    fn resolve_disputed_withdrawal_transaction(mut bank: Bank) {
+        let existing_client = add_client(&mut bank);
+        let disputed_withdrawal = add_disputed_withdrawal_transaction(&mut bank);
+        let resolve = Transaction::Resolve {
+            client: client_of(&disputed_withdrawal),
+            tx: tx_of(&disputed_withdrawal),
+        };
+
+        bank.process_transaction(&resolve).unwrap();
+
+        assert_eq!(
+            bank.accounts.get(&existing_client.id).unwrap(),
+            &Funds::new(existing_client.funds.available, existing_client.funds.held)
+        );
+        assert_eq!(
+            bank.ledger.get(tx_of(&disputed_withdrawal)).unwrap().state,
+            TxState::Resolved
         );
     }
 
@@ -505,55 +944,190 @@ mod tests {
     fn resolve_already_resolved_transaction(mut bank: Bank) {
         add_client(&mut bank);
         let disputed_transaction = add_disputed_transaction(&mut bank);
-        let resolve = Transaction::new(
-            TransactionType::resolve,
-            disputed_transaction.client,
-            disputed_transaction.tx,
-            None,
-        );
+        let resolve = Transaction::Resolve {
+            client: client_of(&disputed_transaction),
+            tx: tx_of(&disputed_transaction),
+        };
 
         bank.process_transaction(&resolve).unwrap();
         let result = bank.process_transaction(&resolve).unwrap_err();
 
-        assert_eq!(result, "Transaction already resolved");
+        assert_eq!(result, LedgerError::AlreadyResolved);
     }
 
-
     #[rstest]
     fn resolve_non_disputed_transaction(mut bank: Bank) {
         let non_disputed_transaction = add_valid_transaction(&mut bank);
-        let resolve = Transaction::new(
-            TransactionType::resolve,
-            non_disputed_transaction.client,
-            non_disputed_transaction.tx,
-            None,
-        );
+        let resolve = Transaction::Resolve {
+            client: client_of(&non_disputed_transaction),
+            tx: tx_of(&non_disputed_transaction),
+        };
 
         let result = bank.process_transaction(&resolve).unwrap_err();
 
-        assert_eq!(result, "Transaction is not disputed");
+        assert_eq!(result, LedgerError::NotDisputed);
+    }
+
+    #[rstest]
+    fn chargeback_disputed_transaction_on_frozen_account_is_rejected(mut bank: Bank) {
+        add_locked_client(&mut bank);
+        let disputed_transaction = add_disputed_transaction(&mut bank);
+        let chargeback = Transaction::Chargeback {
+            client: client_of(&disputed_transaction),
+            tx: tx_of(&disputed_transaction),
+        };
+
+        let result = bank.process_transaction(&chargeback).unwrap_err();
+
+        assert_eq!(result, LedgerError::AccountFrozen);
     }
 
     #[rstest]
     fn chargeback_disputed_transaction(mut bank: Bank) {
         let existing_client = add_client(&mut bank);
         let disputed_transaction = add_disputed_transaction(&mut bank);
-        let resolve = Transaction::new(
-            TransactionType::chargeback,
-            disputed_transaction.client,
-            disputed_transaction.tx,
-            None,
-        );
+        let chargeback = Transaction::Chargeback {
+            client: client_of(&disputed_transaction),
+            tx: tx_of(&disputed_transaction),
+        };
 
-        bank.process_transaction(&resolve).unwrap();
+        bank.process_transaction(&chargeback).unwrap();
 
         assert_eq!(
             bank.accounts.get(&existing_client.id).unwrap(),
             &Funds {
                 available: existing_client.funds.available,
-                held: existing_client.funds.held - disputed_transaction.amount.unwrap(),
+                held: existing_client
+                    .funds
+                    .held
+                    .checked_sub(amount_of(&disputed_transaction))
+                    .unwrap(),
+                is_locked: true
+            }
+        );
+    }
+
+    #[rstest]
+    fn chargeback_disputed_withdrawal_transaction(mut bank: Bank) {
+        let existing_client = add_client(&mut bank);
+        let disputed_withdrawal = add_disputed_withdrawal_transaction(&mut bank);
+        let chargeback = Transaction::Chargeback {
+            client: client_of(&disputed_withdrawal),
+            tx: tx_of(&disputed_withdrawal),
+        };
+
+        bank.process_transaction(&chargeback).unwrap();
+
+        assert_eq!(
+            bank.accounts.get(&existing_client.id).unwrap(),
+            &Funds {
+                available: existing_client
+                    .funds
+                    .available
+                    .checked_add(amount_of(&disputed_withdrawal))
+                    .unwrap(),
+                held: existing_client.funds.held,
                 is_locked: true
             }
         );
     }
+
+    #[rstest]
+    fn run_applies_every_row_from_a_reader(mut bank: Bank) {
+        let csv = "kind,client,tx,amount\ndeposit,1,1,5.0\nwithdrawal,1,2,2.0\n";
+        let mut errors = Vec::new();
+
+        bank.run(io::Cursor::new(csv.as_bytes()), &mut errors).unwrap();
+
+        let mut output = Vec::new();
+        bank.print_accounts(&mut output).unwrap();
+
+        assert!(errors.is_empty());
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "client, available, held, total, locked\n1,3.0000,0.0000,3.0000,false\n"
+        );
+    }
+
+    #[test]
+    fn process_parallel_credits_multiple_clients_independently() {
+        let transactions = vec![
+            Transaction::Deposit {
+                client: 1,
+                tx: 1,
+                amount: Money::from_f64(10.0),
+            },
+            Transaction::Deposit {
+                client: 2,
+                tx: 2,
+                amount: Money::from_f64(20.0),
+            },
+        ];
+        let mut errors = Vec::new();
+
+        let bank = Bank::process_parallel(transactions, &mut errors).unwrap();
+
+        assert!(errors.is_empty());
+        assert_eq!(
+            bank.accounts.get(&1).unwrap(),
+            &Funds::new(Money::from_f64(10.0), Money::ZERO)
+        );
+        assert_eq!(
+            bank.accounts.get(&2).unwrap(),
+            &Funds::new(Money::from_f64(20.0), Money::ZERO)
+        );
+    }
+
+    #[test]
+    fn process_parallel_reports_processing_errors_to_the_writer() {
+        let transactions = vec![Transaction::Withdrawal {
+            client: 1,
+            tx: 1,
+            amount: Money::from_f64(5.0),
+        }];
+        let mut errors = Vec::new();
+
+        let bank = Bank::process_parallel(transactions, &mut errors).unwrap();
+
+        assert_eq!(
+            bank.accounts.get(&1).unwrap(),
+            &Funds::new(Money::ZERO, Money::ZERO)
+        );
+        assert_eq!(String::from_utf8(errors).unwrap(), "insufficient funds\n");
+    }
+
+    #[test]
+    fn process_parallel_reports_unknown_tx_for_a_cross_client_dispute() {
+        let transactions = vec![
+            Transaction::Deposit {
+                client: 1,
+                tx: 1,
+                amount: Money::from_f64(10.0),
+            },
+            Transaction::Dispute { client: 2, tx: 1 },
+        ];
+        let mut errors = Vec::new();
+
+        Bank::process_parallel(transactions, &mut errors).unwrap();
+
+        assert_eq!(String::from_utf8(errors).unwrap(), "transaction 1 not found\n");
+    }
+
+    #[test]
+    fn parse_deposit_missing_amount_is_rejected() {
+        let record = TransactionRecord::new(TransactionType::deposit, 1, 1, None);
+
+        let result = Transaction::try_from(record).unwrap_err();
+
+        assert_eq!(result, ParseError::MissingAmount);
+    }
+
+    #[test]
+    fn parse_dispute_without_amount_succeeds() {
+        let record = TransactionRecord::new(TransactionType::dispute, 1, 1, None);
+
+        let transaction = Transaction::try_from(record).unwrap();
+
+        assert_eq!(transaction, Transaction::Dispute { client: 1, tx: 1 });
+    }
 }
@@ -0,0 +1,29 @@
+use thiserror::Error;
+
+/// Everything that can go wrong while applying a [`crate::bank::Transaction`]
+/// to the ledger. Kept structured (rather than `&str`) so callers can match
+/// on the kind of failure instead of comparing English sentences, and so
+/// `main` can route these to stderr while stdout stays pure CSV.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum LedgerError {
+    #[error("insufficient funds")]
+    NotEnoughFunds,
+    #[error("account is frozen")]
+    AccountFrozen,
+    #[error("client not found")]
+    ClientNotFound,
+    #[error("transaction {0} not found")]
+    UnknownTx(u32),
+    #[error("cannot dispute a transaction belonging to another client")]
+    DisputeClientMismatch,
+    #[error("transaction is already disputed")]
+    AlreadyDisputed,
+    #[error("transaction is not disputed")]
+    NotDisputed,
+    #[error("transaction has already been resolved")]
+    AlreadyResolved,
+    #[error("transaction has already been charged back")]
+    AlreadyChargedBack,
+    #[error("upper limit reached, time to give to charity?")]
+    UpperLimitReached,
+}
@@ -0,0 +1,169 @@
+use crate::bank::TxState;
+use crate::money::Money;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// What the ledger remembers about a deposit or withdrawal once it has been
+/// applied, so a later dispute/resolve/chargeback can be validated against
+/// it.
+///
+/// `amount` is signed: positive for a deposit, negative for a withdrawal.
+/// The sign lets dispute/resolve/chargeback fold the held-funds adjustment
+/// into a single `checked_add`/`checked_sub`, while still telling the two
+/// transaction kinds apart where the effect on `available` differs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LedgerRecord {
+    pub(crate) client: u16,
+    pub(crate) amount: Money,
+    pub(crate) state: TxState,
+}
+
+impl LedgerRecord {
+    pub(crate) fn new(client: u16, amount: Money) -> Self {
+        Self {
+            client,
+            amount,
+            state: TxState::Processed,
+        }
+    }
+
+    fn to_bytes(self) -> [u8; 11] {
+        let mut buf = [0u8; 11];
+        buf[0..2].copy_from_slice(&self.client.to_be_bytes());
+        buf[2..10].copy_from_slice(&self.amount.to_ten_thousandths().to_be_bytes());
+        buf[10] = self.state.to_byte();
+        buf
+    }
+
+    fn from_bytes(buf: &[u8]) -> Self {
+        let client = u16::from_be_bytes(buf[0..2].try_into().unwrap());
+        let amount = Money::from_ten_thousandths(i64::from_be_bytes(buf[2..10].try_into().unwrap()));
+        let state = TxState::from_byte(buf[10]);
+        Self {
+            client,
+            amount,
+            state,
+        }
+    }
+}
+
+/// Abstracts where transaction records live, so `Bank` can keep dispute
+/// lookups working without being forced to hold every transaction in memory
+/// forever.
+pub trait LedgerStore {
+    fn get(&self, tx: u32) -> Option<LedgerRecord>;
+    fn insert(&mut self, tx: u32, record: LedgerRecord);
+    fn update_state(&mut self, tx: u32, state: TxState);
+}
+
+/// Keeps every transaction record in a `HashMap`. Simple and fast, but
+/// memory use grows with the size of the input stream.
+#[derive(Default)]
+pub struct InMemoryLedgerStore {
+    records: HashMap<u32, LedgerRecord>,
+}
+
+impl LedgerStore for InMemoryLedgerStore {
+    fn get(&self, tx: u32) -> Option<LedgerRecord> {
+        self.records.get(&tx).copied()
+    }
+
+    fn insert(&mut self, tx: u32, record: LedgerRecord) {
+        self.records.insert(tx, record);
+    }
+
+    fn update_state(&mut self, tx: u32, state: TxState) {
+        if let Some(record) = self.records.get_mut(&tx) {
+            record.state = state;
+        }
+    }
+}
+
+/// Spills transaction records to an embedded on-disk key-value store keyed
+/// by `tx`, so memory stays bounded on inputs larger than RAM. Dispute
+/// lookups remain a single keyed read.
+pub struct DiskLedgerStore {
+    db: sled::Db,
+}
+
+impl DiskLedgerStore {
+    pub fn open(path: impl AsRef<Path>) -> sled::Result<Self> {
+        Ok(Self {
+            db: sled::open(path)?,
+        })
+    }
+}
+
+impl LedgerStore for DiskLedgerStore {
+    fn get(&self, tx: u32) -> Option<LedgerRecord> {
+        match self.db.get(tx.to_be_bytes()) {
+            Ok(bytes) => bytes.map(|bytes| LedgerRecord::from_bytes(&bytes)),
+            Err(e) => {
+                // A read failure here is not the same as "no record for this
+                // tx" — the caller would otherwise see a disk error as
+                // `LedgerError::UnknownTx`, so at least get it onto stderr.
+                eprintln!("ledger store: failed to read tx {tx}: {e}");
+                None
+            }
+        }
+    }
+
+    fn insert(&mut self, tx: u32, record: LedgerRecord) {
+        if let Err(e) = self.db.insert(tx.to_be_bytes(), &record.to_bytes()[..]) {
+            eprintln!("ledger store: failed to write tx {tx}: {e}");
+        }
+    }
+
+    fn update_state(&mut self, tx: u32, state: TxState) {
+        if let Some(mut record) = self.get(tx) {
+            record.state = state;
+            self.insert(tx, record);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_store() -> (DiskLedgerStore, std::path::PathBuf) {
+        let path = std::env::temp_dir().join(format!(
+            "toy-payment-engine-test-{}-{}",
+            std::process::id(),
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        (DiskLedgerStore::open(&path).unwrap(), path)
+    }
+
+    #[test]
+    fn disk_store_round_trips_a_record() {
+        let (mut store, path) = temp_store();
+        let record = LedgerRecord::new(7, Money::from_f64(12.3456));
+
+        store.insert(42, record);
+
+        assert_eq!(store.get(42), Some(record));
+        assert_eq!(store.get(43), None);
+
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    #[test]
+    fn disk_store_update_state_changes_only_the_state() {
+        let (mut store, path) = temp_store();
+        let record = LedgerRecord::new(7, Money::from_f64(12.3456));
+        store.insert(1, record);
+
+        store.update_state(1, TxState::Disputed);
+
+        let updated = store.get(1).unwrap();
+        assert_eq!(updated.state, TxState::Disputed);
+        assert_eq!(updated.client, record.client);
+        assert_eq!(updated.amount, record.amount);
+
+        let _ = std::fs::remove_dir_all(path);
+    }
+}
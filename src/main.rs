@@ -1,54 +1,102 @@
-use std::{collections::HashMap, io};
 use std::env;
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{self, BufReader, Write};
 mod bank;
+mod errors;
+mod ledger_store;
+mod money;
+
+/// Where CSV input for this run comes from: standard input (no file
+/// arguments given, or the lone argument is `-`), or one or more named
+/// files, read and applied to the same `Bank` in the order given.
+enum Input {
+    Stdin,
+    Files(Vec<String>),
+}
+
+fn open_reader(filename: &str) -> io::Result<BufReader<File>> {
+    let file = File::open(filename)?;
+    Ok(BufReader::with_capacity(1000, file))
+}
 
 fn main() -> io::Result<()> {
-    let mut bank = bank::Bank {
-        accounts: HashMap::new(),
-        ledger: HashMap::new(),
+    let mut parallel = false;
+    let mut ledger_store_path: Option<String> = None;
+    let mut filenames = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--parallel" => parallel = true,
+            "--ledger-store-path" => ledger_store_path = args.next(),
+            "-" => {}
+            _ => filenames.push(arg),
+        }
+    }
+    let input = if filenames.is_empty() {
+        Input::Stdin
+    } else {
+        Input::Files(filenames)
     };
 
-    let csv_filename = env::args().nth(1);
-    let file = File::open(csv_filename.unwrap())?;
-    let buffer_size = 1000;
-    let reader = BufReader::with_capacity(buffer_size,file);
-
-    let mut rdr = csv::ReaderBuilder::new()
-        .trim(csv::Trim::All)
-        .flexible(true)
-        .from_reader(reader);
-
-        for record in rdr.deserialize() {
-            match record as Result<bank::Transaction, csv::Error> {
-                Ok(transaction) => {
-                    if (transaction.kind == bank::TransactionType::deposit
-                        || transaction.kind == bank::TransactionType::withdrawal)
-                        && transaction.amount.is_none()
-                    {
-                        println!("Amount is missing");
-                        continue;
-                    }
-
-                    match bank.process_transaction(&transaction) {
-                        Ok(_) => {
-                            if transaction.kind == bank::TransactionType::deposit
-                                || transaction.kind == bank::TransactionType::withdrawal
-                            {
-                                bank.add_transaction_to_ledger(transaction);
-                            }
-                        }
-                        Err(e) => println!("{}", e),
-                    }
-                }
-                Err(e) => {
-                    println!("{}", e);
-                    continue;
-                }
-            }
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let stderr = io::stderr();
+    let mut err = stderr.lock();
+
+    if parallel {
+        let transactions = read_transactions(input, &mut err)?;
+        let bank = bank::Bank::process_parallel(transactions, &mut err)?;
+        bank.print_accounts(&mut out)?;
+        return Ok(());
+    }
+
+    match ledger_store_path {
+        // Spills to disk instead of holding every transaction in memory,
+        // for inputs too large to fit in RAM.
+        Some(path) => {
+            let store = ledger_store::DiskLedgerStore::open(path)
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+            let bank = run_sequential(bank::Bank::with_store(store), input, &mut err)?;
+            bank.print_accounts(&mut out)?;
+        }
+        None => {
+            let bank = run_sequential(bank::Bank::new(), input, &mut err)?;
+            bank.print_accounts(&mut out)?;
         }
+    }
 
-    bank.print_accounts();
     Ok(())
 }
+
+/// Feeds `input` into `bank` one reader at a time, so stdin or several
+/// files in sequence fold into the one shared ledger.
+fn run_sequential<S: ledger_store::LedgerStore>(
+    mut bank: bank::Bank<S>,
+    input: Input,
+    errors: &mut impl Write,
+) -> io::Result<bank::Bank<S>> {
+    match input {
+        Input::Stdin => bank.run(io::stdin().lock(), errors)?,
+        Input::Files(filenames) => {
+            for filename in filenames {
+                bank.run(open_reader(&filename)?, errors)?;
+            }
+        }
+    }
+    Ok(bank)
+}
+
+/// Parses every input's transactions up front, used by `--parallel` since
+/// sharding by client needs the whole stream before any processing starts.
+fn read_transactions<W: Write>(input: Input, errors: &mut W) -> io::Result<Vec<bank::Transaction>> {
+    match input {
+        Input::Stdin => bank::parse_transactions(io::stdin().lock(), errors),
+        Input::Files(filenames) => {
+            let mut transactions = Vec::new();
+            for filename in filenames {
+                transactions.extend(bank::parse_transactions(open_reader(&filename)?, errors)?);
+            }
+            Ok(transactions)
+        }
+    }
+}
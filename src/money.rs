@@ -0,0 +1,110 @@
+use serde::{Deserialize, Deserializer};
+use std::fmt;
+
+/// Number of ten-thousandths per whole unit; the ledger fixes precision at
+/// four decimal places.
+const SCALE: i64 = 10_000;
+
+/// A monetary amount stored as an integer count of ten-thousandths, so
+/// repeated deposits/withdrawals never accumulate floating point rounding
+/// error. Values are parsed (and rounded) once on input and all arithmetic
+/// afterwards is exact integer math.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct Money(i64);
+
+impl Money {
+    pub const ZERO: Money = Money(0);
+
+    /// Builds a `Money` from a raw decimal value, rounding to the nearest
+    /// ten-thousandth. This is the single point where float imprecision is
+    /// allowed to enter the system.
+    pub fn from_f64(value: f64) -> Self {
+        Money((value * SCALE as f64).round() as i64)
+    }
+
+    /// Round-trips through the raw ten-thousandths count, used by storage
+    /// backends that need to persist a `Money` value as plain bytes.
+    pub fn to_ten_thousandths(self) -> i64 {
+        self.0
+    }
+
+    pub fn from_ten_thousandths(raw: i64) -> Self {
+        Money(raw)
+    }
+
+    pub fn checked_add(self, other: Money) -> Option<Money> {
+        self.0.checked_add(other.0).map(Money)
+    }
+
+    pub fn checked_sub(self, other: Money) -> Option<Money> {
+        self.0.checked_sub(other.0).map(Money)
+    }
+}
+
+impl std::ops::Neg for Money {
+    type Output = Money;
+
+    fn neg(self) -> Money {
+        Money(-self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let raw = f64::deserialize(deserializer)?;
+        Ok(Money::from_f64(raw))
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let whole = self.0 / SCALE;
+        let frac = (self.0 % SCALE).abs();
+        // `whole` truncates to 0 for |self.0| < SCALE, losing the sign that
+        // integer division would otherwise carry, so a negative
+        // sub-one-unit value needs it put back explicitly.
+        if self.0 < 0 && whole == 0 {
+            write!(f, "-{}.{:04}", whole, frac)
+        } else {
+            write!(f, "{}.{:04}", whole, frac)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_f64_rounds_to_four_decimals() {
+        assert_eq!(Money::from_f64(1.00005).0, 10_001);
+        assert_eq!(Money::from_f64(42.1234).0, 421_234);
+    }
+
+    #[test]
+    fn display_always_shows_four_decimals() {
+        assert_eq!(Money::from_f64(1.5).to_string(), "1.5000");
+        assert_eq!(Money::from_f64(0.0001).to_string(), "0.0001");
+    }
+
+    #[test]
+    fn display_keeps_the_sign_for_a_negative_fraction_below_one() {
+        assert_eq!(Money::from_f64(-0.5).to_string(), "-0.5000");
+        assert_eq!(Money::from_f64(-5.0).to_string(), "-5.0000");
+    }
+
+    #[test]
+    fn checked_add_detects_overflow() {
+        let max = Money(i64::MAX);
+        assert_eq!(max.checked_add(Money::from_f64(1.0)), None);
+    }
+
+    #[test]
+    fn neg_flips_the_sign() {
+        assert_eq!(-Money::from_f64(5.0), Money::from_f64(-5.0));
+        assert_eq!(-Money::ZERO, Money::ZERO);
+    }
+}